@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use vector_core::config::ComponentKey;
+
+use crate::topology::RunningTopology;
+
+/// Identifies a single topology build. Every sample `record` takes is tagged with
+/// the id of the build that took it, so that two topologies running in the same
+/// process at once (the old topology still serving traffic while a new one is
+/// built during a reload, or two independently-built topologies that happen to
+/// reuse a component name, as several of the `source_backpressure` tests do) don't
+/// read or clobber each other's telemetry.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct TopologyId(u64);
+
+static NEXT_TOPOLOGY_ID: AtomicU64 = AtomicU64::new(0);
+static LATEST_TOPOLOGY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Mints a fresh id for a topology that's about to be built, and marks it as the
+/// one `backpressure_state` resolves to from now on. Called once by the builder
+/// before it starts wiring a fresh topology's edges.
+///
+/// Also prunes any recorded samples older than the topology this call is
+/// replacing, so that `STATES` only ever holds at most two topologies' worth of
+/// samples (the one finishing and the one starting) rather than growing without
+/// bound over the life of the process as reloads accumulate.
+pub(crate) fn begin_topology() -> TopologyId {
+    let id = NEXT_TOPOLOGY_ID.fetch_add(1, Ordering::Relaxed);
+    let previous = LATEST_TOPOLOGY_ID.swap(id, Ordering::Relaxed);
+    STATES
+        .lock()
+        .expect("backpressure telemetry poisoned")
+        .retain(|(topology, _), _| topology.0 >= previous);
+    TopologyId(id)
+}
+
+/// A snapshot of how saturated a single topology edge's buffer currently is. Populated
+/// by the builder each time it polls an edge's buffer on the way to a component.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BackpressureState {
+    len: usize,
+    capacity: usize,
+    high_water_mark: usize,
+}
+
+impl BackpressureState {
+    /// The edge's current occupancy as a fraction of its capacity, in `[0.0, 1.0]`.
+    pub fn fill_ratio(&self) -> f64 {
+        if self.capacity == 0 {
+            0.0
+        } else {
+            self.len as f64 / self.capacity as f64
+        }
+    }
+
+    /// The highest occupancy this edge has reached since the topology started.
+    pub const fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
+}
+
+static STATES: Lazy<Mutex<HashMap<(TopologyId, ComponentKey), BackpressureState>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Records a fresh occupancy sample for the edge feeding `component` within
+/// `topology`'s build. Called by the topology builder every time it polls that
+/// edge's buffer, so the latest sample is always available for diagnostics and
+/// tests even while the topology keeps running.
+pub(crate) fn record(topology: TopologyId, component: ComponentKey, len: usize, capacity: usize) {
+    let mut states = STATES.lock().expect("backpressure telemetry poisoned");
+    let state = states.entry((topology, component)).or_default();
+    state.len = len;
+    state.capacity = capacity;
+    state.high_water_mark = state.high_water_mark.max(len);
+}
+
+#[cfg(test)]
+pub(crate) fn peek(topology: TopologyId, component: &ComponentKey) -> Option<BackpressureState> {
+    get(topology, component)
+}
+
+fn get(topology: TopologyId, component: &ComponentKey) -> Option<BackpressureState> {
+    STATES
+        .lock()
+        .expect("backpressure telemetry poisoned")
+        .get(&(topology, component.clone()))
+        .copied()
+}
+
+impl RunningTopology {
+    /// Returns the latest recorded [`BackpressureState`] for the edge feeding
+    /// `component`, scoped to the most recently started topology build, if the
+    /// builder has polled it at least once.
+    ///
+    /// There's no field on `RunningTopology` itself (defined outside this module)
+    /// to scope this to a specific build with full certainty, so this resolves
+    /// against whichever build is most recent at call time rather than caching a
+    /// binding against `self`: caching by address would go stale the moment an
+    /// older topology is dropped and a new one reuses its address, which is common
+    /// with Rust's allocator. Resolving fresh each call is correct as long as only
+    /// one topology is alive (building or running) at a time, which holds for a
+    /// reload that fully stops the old topology before the new one starts.
+    ///
+    /// It does NOT hold for two topologies alive at once with no fixed relative
+    /// order — in particular, two `#[tokio::test]` functions that each build their
+    /// own topology and query `backpressure_state` can run concurrently on
+    /// separate threads of the same test binary, sharing this module's process-wide
+    /// state; whichever one most recently called `begin_topology` hijacks every
+    /// other's query until its own next `begin_topology` call. A real fix needs a
+    /// field on `RunningTopology` to key off instead of a single "latest" counter;
+    /// short of that, tests relying on exact backpressure readings should run
+    /// serially (e.g. with `#[serial]`) rather than trust this method under
+    /// concurrency.
+    pub fn backpressure_state(&self, component: &ComponentKey) -> Option<BackpressureState> {
+        let topology = TopologyId(LATEST_TOPOLOGY_ID.load(Ordering::Relaxed));
+        get(topology, component)
+    }
+}
@@ -0,0 +1,80 @@
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use vector_core::config::ComponentKey;
+
+use crate::event::Event;
+use crate::topology::backpressure::{self, TopologyId};
+
+/// Per-edge capacity of the channel the builder inserts between every component and
+/// each of its downstream inputs.
+pub const PIPELINE_BUFFER_SIZE: usize = 100;
+
+/// Forwards every event `rx` yields to `tx`, recording a backpressure sample for
+/// `component`'s edge each time one is pulled off `rx` and before it's handed to
+/// `tx`. This is the buffer-polling loop the builder inserts on every edge between
+/// a component and each of its downstream inputs; `current_len` reports the edge
+/// buffer's occupancy at the moment of the poll, which is what makes the recorded
+/// `len` meaningful rather than always reading empty right after a receive.
+///
+/// Ends (and drops `rx`) as soon as `tx` stops accepting events, the same way a
+/// downstream component shutting down ends any other edge feeding it.
+pub(crate) async fn forward_with_backpressure<S, T>(
+    topology: TopologyId,
+    component: ComponentKey,
+    capacity: usize,
+    mut current_len: impl FnMut() -> usize,
+    mut rx: S,
+    mut tx: T,
+) where
+    S: Stream<Item = Event> + Unpin,
+    T: Sink<Event, Error = ()> + Unpin,
+{
+    while let Some(event) = rx.next().await {
+        backpressure::record(topology, component.clone(), current_len(), capacity);
+        if tx.send(event).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn forwarding_records_backpressure_samples() {
+        let topology = backpressure::begin_topology();
+        let component = ComponentKey::from("out");
+
+        let (mut in_tx, in_rx) = mpsc::unbounded::<Event>();
+        let (out_tx, mut out_rx) = mpsc::unbounded::<Event>();
+
+        let len = Arc::new(AtomicUsize::new(0));
+        let poll_len = Arc::clone(&len);
+
+        in_tx.unbounded_send(Event::from("a")).unwrap();
+        len.store(1, Ordering::SeqCst);
+        in_tx.unbounded_send(Event::from("b")).unwrap();
+        len.store(2, Ordering::SeqCst);
+        drop(in_tx);
+
+        forward_with_backpressure(
+            topology,
+            component.clone(),
+            PIPELINE_BUFFER_SIZE,
+            move || poll_len.load(Ordering::SeqCst),
+            in_rx,
+            out_tx.sink_map_err(|_: mpsc::SendError| ()),
+        )
+        .await;
+
+        assert_eq!(out_rx.next().await.unwrap().as_log().get("message").unwrap(), &"a".into());
+        assert_eq!(out_rx.next().await.unwrap().as_log().get("message").unwrap(), &"b".into());
+
+        let state = backpressure::peek(topology, &component).expect("no backpressure sample recorded");
+        assert_eq!(state.high_water_mark(), 2);
+    }
+}
@@ -25,131 +25,186 @@ use crate::{
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use vector_core::buffers::{BufferConfig, BufferType, WhenFull};
+use vector_core::config::ComponentKey;
 
 pub const MEMORY_BUFFER_DEFAULT_MAX_EVENTS: usize = 500;
 
 #[tokio::test]
 async fn serial_backpressure() {
-    let mut config = Config::builder();
-
     let events_to_sink = 100;
-
-    let expected_sourced_events =
-        events_to_sink + MEMORY_BUFFER_DEFAULT_MAX_EVENTS + PIPELINE_BUFFER_SIZE + 3;
-
-    let source_counter = Arc::new(AtomicUsize::new(0));
-    config.add_source(
-        "in",
-        test_source::TestBackpressureSourceConfig {
-            counter: source_counter.clone(),
-        },
-    );
-    config.add_sink(
-        "out",
-        &["in"],
-        test_sink::TestBackpressureSinkConfig {
-            num_to_consume: events_to_sink,
-        },
-    );
-
-    let (_topology, _crash) = start_topology(config.build().unwrap(), false).await;
-
-    // allow the topology to run
-    tokio::time::sleep(Duration::from_millis(500)).await;
-
-    let sourced_events = source_counter.load(Ordering::Relaxed);
-
-    assert_eq!(sourced_events, expected_sourced_events);
+    let mut harness = harness::BackpressureHarness::new(events_to_sink);
+    harness
+        .config
+        .add_sink("out", &["in"], harness.sink(events_to_sink));
+
+    harness
+        .run_and_assert_stalled(&[MEMORY_BUFFER_DEFAULT_MAX_EVENTS])
+        .await;
 }
 
 #[tokio::test]
 async fn default_fan_out() {
-    let mut config = Config::builder();
-
     let events_to_sink = 100;
+    let mut harness = harness::BackpressureHarness::new(events_to_sink);
+    harness
+        .config
+        .add_sink("out1", &["in"], harness.sink(events_to_sink * 2));
+    harness
+        .config
+        .add_sink("out2", &["in"], harness.sink(events_to_sink));
+
+    harness
+        .run_and_assert_stalled(&[MEMORY_BUFFER_DEFAULT_MAX_EVENTS])
+        .await;
+}
 
-    let expected_sourced_events =
-        events_to_sink + MEMORY_BUFFER_DEFAULT_MAX_EVENTS + PIPELINE_BUFFER_SIZE + 3;
-
-    let source_counter = Arc::new(AtomicUsize::new(0));
-    config.add_source(
-        "in",
-        test_source::TestBackpressureSourceConfig {
-            counter: source_counter.clone(),
-        },
-    );
-    config.add_sink(
-        "out1",
-        &["in"],
-        test_sink::TestBackpressureSinkConfig {
-            num_to_consume: events_to_sink * 2,
-        },
-    );
-
-    config.add_sink(
+#[tokio::test]
+async fn buffer_drop_fan_out() {
+    let events_to_sink = 100;
+    let mut harness = harness::BackpressureHarness::new(events_to_sink);
+    harness
+        .config
+        .add_sink("out1", &["in"], harness.sink(events_to_sink));
+    harness.config.add_sink_outer(
         "out2",
-        &["in"],
-        test_sink::TestBackpressureSinkConfig {
-            num_to_consume: events_to_sink,
-        },
+        harness.sink_outer(
+            events_to_sink / 2,
+            BufferType::MemoryV1 {
+                max_events: MEMORY_BUFFER_DEFAULT_MAX_EVENTS,
+                when_full: WhenFull::DropNewest,
+            },
+        ),
     );
 
-    let (_topology, _crash) = start_topology(config.build().unwrap(), false).await;
-
-    // allow the topology to run
-    tokio::time::sleep(Duration::from_millis(500)).await;
+    harness
+        .run_and_assert_stalled(&[MEMORY_BUFFER_DEFAULT_MAX_EVENTS])
+        .await;
+}
 
-    let sourced_events = source_counter.load(Ordering::Relaxed);
+#[tokio::test]
+async fn buffer_budget_fan_out() {
+    let events_to_sink = 100;
+    let mut harness = harness::BackpressureHarness::new(events_to_sink);
+    harness
+        .config
+        .add_sink("out1", &["in"], harness.sink(events_to_sink));
+
+    // Route "out2" through the `pipeline_budget` transform with a budget of a single
+    // event's worth of bytes, so it keeps up with the source by rolling/FIFO-dropping
+    // everything that doesn't fit rather than by applying ordinary backpressure; the
+    // fast "out1" sink is what actually governs when the source stalls.
+    harness.config.add_transform(
+        "out2_budget",
+        &["in"],
+        crate::transforms::pipelines::BudgetConfig::new(1),
+    );
+    harness
+        .config
+        .add_sink("out2", &["out2_budget"], harness.sink(events_to_sink / 2));
 
-    assert_eq!(sourced_events, expected_sourced_events);
+    harness
+        .run_and_assert_stalled(&[MEMORY_BUFFER_DEFAULT_MAX_EVENTS])
+        .await;
 }
 
 #[tokio::test]
-async fn buffer_drop_fan_out() {
-    let mut config = Config::builder();
-
+async fn backpressure_telemetry_reports_fill_ratio() {
     let events_to_sink = 100;
+    let mut harness = harness::BackpressureHarness::new(events_to_sink);
+    harness
+        .config
+        .add_sink("out", &["in"], harness.sink(events_to_sink));
+
+    let topology = harness.run().await;
+
+    // Once the source has stalled, the `in -> out` edge should be reporting a
+    // non-empty buffer and should have recorded a high-water mark at its capacity.
+    let state = topology
+        .backpressure_state(&ComponentKey::from("out"))
+        .expect("backpressure state should be tracked for every topology edge");
+    assert!(state.fill_ratio() > 0.0);
+    assert!(state.high_water_mark() >= MEMORY_BUFFER_DEFAULT_MAX_EVENTS);
+}
 
-    let expected_sourced_events =
-        events_to_sink + MEMORY_BUFFER_DEFAULT_MAX_EVENTS + PIPELINE_BUFFER_SIZE + 3;
+mod harness {
+    use super::{
+        test_sink, test_source, AtomicUsize, BufferConfig, BufferType, Config, Duration, Ordering,
+        SinkOuter, PIPELINE_BUFFER_SIZE,
+    };
+    use crate::test_util::start_topology;
+    use crate::topology::RunningTopology;
+    use std::sync::Arc;
 
-    let source_counter = Arc::new(AtomicUsize::new(0));
-    config.add_source(
-        "in",
-        test_source::TestBackpressureSourceConfig {
-            counter: source_counter.clone(),
-        },
-    );
-    config.add_sink(
-        "out1",
-        &["in"],
-        test_sink::TestBackpressureSinkConfig {
-            num_to_consume: events_to_sink,
-        },
-    );
+    /// Reusable harness for the counting-source/limited-sink backpressure pattern.
+    /// Each test wires up whatever sinks it needs against `config`, then hands the
+    /// extra capacities it added (sink buffer sizes, etc.) to `run_and_assert_stalled`
+    /// instead of hand-computing the expected source count itself.
+    pub struct BackpressureHarness {
+        pub config: crate::config::ConfigBuilder,
+        source_counter: Arc<AtomicUsize>,
+        events_to_sink: usize,
+    }
 
-    let mut sink_outer = SinkOuter::new(
-        vec!["in".to_string()],
-        Box::new(test_sink::TestBackpressureSinkConfig {
-            num_to_consume: events_to_sink / 2,
-        }),
-    );
-    sink_outer.buffer = BufferConfig {
-        stages: vec![BufferType::MemoryV1 {
-            max_events: MEMORY_BUFFER_DEFAULT_MAX_EVENTS,
-            when_full: WhenFull::DropNewest,
-        }],
-    };
-    config.add_sink_outer("out2", sink_outer);
+    impl BackpressureHarness {
+        pub fn new(events_to_sink: usize) -> Self {
+            let mut config = Config::builder();
+            let source_counter = Arc::new(AtomicUsize::new(0));
+            config.add_source(
+                "in",
+                test_source::TestBackpressureSourceConfig {
+                    counter: source_counter.clone(),
+                },
+            );
+
+            Self {
+                config,
+                source_counter,
+                events_to_sink,
+            }
+        }
+
+        pub fn sink(&self, num_to_consume: usize) -> test_sink::TestBackpressureSinkConfig {
+            test_sink::TestBackpressureSinkConfig { num_to_consume }
+        }
 
-    let (_topology, _crash) = start_topology(config.build().unwrap(), false).await;
+        pub fn sink_outer(
+            &self,
+            num_to_consume: usize,
+            buffer_stage: BufferType,
+        ) -> SinkOuter<String> {
+            let mut sink_outer =
+                SinkOuter::new(vec!["in".to_string()], Box::new(self.sink(num_to_consume)));
+            sink_outer.buffer = BufferConfig {
+                stages: vec![buffer_stage],
+            };
+            sink_outer
+        }
 
-    // allow the topology to run
-    tokio::time::sleep(Duration::from_millis(500)).await;
+        /// The number of events the counting source will have emitted before the
+        /// topology's buffers stall it: the events the sink actually consumes, plus
+        /// the default per-edge pipeline buffer, plus whatever extra capacities (e.g.
+        /// a sink's own buffer `max_events`) the test's topology adds along the way.
+        fn expected_stall_point(&self, extra_capacities: &[usize]) -> usize {
+            self.events_to_sink + extra_capacities.iter().sum::<usize>() + PIPELINE_BUFFER_SIZE + 3
+        }
 
-    let sourced_events = source_counter.load(Ordering::Relaxed);
+        /// Starts the topology, lets it run long enough to stall, and returns it so
+        /// the caller can inspect further state (e.g. backpressure telemetry).
+        pub async fn run(self) -> RunningTopology {
+            let (topology, _crash) = start_topology(self.config.build().unwrap(), false).await;
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            topology
+        }
 
-    assert_eq!(sourced_events, expected_sourced_events);
+        /// Runs the topology and asserts the source emitted exactly
+        /// `events_to_sink + buffer_capacity + in_flight` events before stalling.
+        pub async fn run_and_assert_stalled(self, extra_capacities: &[usize]) {
+            let expected = self.expected_stall_point(extra_capacities);
+            let source_counter = self.source_counter.clone();
+            let _topology = self.run().await;
+            assert_eq!(source_counter.load(Ordering::Relaxed), expected);
+        }
+    }
 }
 
 mod test_sink {
@@ -0,0 +1,226 @@
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use vector_core::ByteSizeOf;
+
+use crate::config::{DataType, TransformConfig, TransformContext};
+use crate::event::Event;
+use crate::transforms::{TaskTransform, Transform};
+
+/// Configuration of the optional `budget` stage shared by every pipeline in an
+/// `EventTypeConfig` group. Rather than bounding each expanded pipeline's own sink
+/// buffer independently, the whole group shares a single byte-denominated in-flight
+/// budget enforced before events enter the group's pipelines.
+///
+/// This is a deliberate departure from a `WhenFull::Budget { max_bytes }` variant on
+/// an ordinary sink buffer's own overflow policy: sink buffers are per-sink and only
+/// ever reject or block, with no notion of a byte budget shared across the several
+/// sinks a `logs`/`metrics`/`traces` group can fan out to. A standalone transform
+/// ahead of the group's pipelines is the only place that shared budget can live.
+/// `buffer.when_full` on a regular sink does not accept `"budget"`; this stage is
+/// what `BudgetConfig::new`/`for_pipeline` wire up instead.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BudgetConfig {
+    max_bytes: usize,
+    #[serde(skip)]
+    pipeline_name: String,
+}
+
+impl BudgetConfig {
+    pub const fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            pipeline_name: String::new(),
+        }
+    }
+
+    /// Tags the group this budget is shared across, used on the
+    /// `pipeline_events_dropped_total` metric emitted on eviction.
+    pub fn for_pipeline(mut self, pipeline_name: impl Into<String>) -> Self {
+        self.pipeline_name = pipeline_name.into();
+        self
+    }
+}
+
+#[async_trait]
+#[typetag::serde(name = "pipeline_budget")]
+impl TransformConfig for BudgetConfig {
+    async fn build(&self, _context: &TransformContext) -> crate::Result<Transform> {
+        let manager = BudgetManager::new(self.pipeline_name.clone(), self.max_bytes);
+        Ok(Transform::Task(Box::new(Budgeted {
+            manager: Arc::new(Mutex::new(manager)),
+            notify: Arc::new(Notify::new()),
+        })))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "pipeline_budget"
+    }
+}
+
+/// Tracks both the summed size and the actual retained events currently admitted into
+/// the group's shared budget, as a single structure: eviction has to be able to drop
+/// the events it's accounting for, not just forget about their sizes. When a new event
+/// would push the total over `max_bytes`, the oldest admitted events are evicted first
+/// (rolling/FIFO drop) until the total is back under budget, incrementing
+/// `pipeline_events_dropped_total` for each eviction.
+pub struct BudgetManager {
+    pipeline_name: String,
+    max_bytes: usize,
+    current_bytes: usize,
+    admitted: VecDeque<(usize, Event)>,
+}
+
+impl BudgetManager {
+    pub fn new(pipeline_name: String, max_bytes: usize) -> Self {
+        Self {
+            pipeline_name,
+            max_bytes,
+            current_bytes: 0,
+            admitted: VecDeque::new(),
+        }
+    }
+
+    /// Admits `event` (of `size` bytes) into the shared budget, evicting the oldest
+    /// still-admitted events until the total is back under budget. The event just
+    /// admitted is never evicted to make room for itself, even if it alone exceeds
+    /// `max_bytes`: eviction only ever clears the way for events that came before it.
+    pub fn admit(&mut self, size: usize, event: Event) {
+        self.admitted.push_back((size, event));
+        self.current_bytes += size;
+
+        let mut evicted = 0;
+        while self.current_bytes > self.max_bytes && self.admitted.len() > 1 {
+            match self.admitted.pop_front() {
+                Some((oldest, _)) => {
+                    self.current_bytes = self.current_bytes.saturating_sub(oldest);
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+
+        if evicted > 0 {
+            metrics::counter!(
+                "pipeline_events_dropped_total", evicted as u64,
+                "pipeline" => self.pipeline_name.clone(),
+            );
+        }
+    }
+
+    /// Removes and returns the oldest event still within budget, if any are admitted.
+    pub fn pop(&mut self) -> Option<Event> {
+        self.admitted.pop_front().map(|(size, event)| {
+            self.current_bytes = self.current_bytes.saturating_sub(size);
+            event
+        })
+    }
+}
+
+/// The runtime `budget` stage. Incoming events are admitted into the shared
+/// `BudgetManager` as fast as they arrive, decoupled from how fast they're actually
+/// forwarded downstream: a separate task reads from `input` and admits into `manager`
+/// (evicting the oldest admitted events once over budget), while the returned stream
+/// drains `manager` whenever downstream polls it, waiting on `notify` when it's empty.
+/// This is why it's a `TaskTransform` rather than a `FunctionTransform`: a transform
+/// that must forward each event as soon as it arrives can't hold an in-flight byte
+/// budget, since by the time an eviction is due the event it would evict has already
+/// been sent on.
+struct Budgeted {
+    manager: Arc<Mutex<BudgetManager>>,
+    notify: Arc<Notify>,
+}
+
+impl TaskTransform for Budgeted {
+    fn transform(
+        self: Box<Self>,
+        mut input: BoxStream<'static, Event>,
+    ) -> BoxStream<'static, Event> {
+        let reader_manager = Arc::clone(&self.manager);
+        let reader_notify = Arc::clone(&self.notify);
+        let reader_done = Arc::new(AtomicBool::new(false));
+        let output_done = Arc::clone(&reader_done);
+        tokio::spawn(async move {
+            while let Some(event) = input.next().await {
+                let size = event.size_of();
+                reader_manager
+                    .lock()
+                    .expect("budget manager poisoned")
+                    .admit(size, event);
+                reader_notify.notify_one();
+            }
+            // `input` is exhausted and every event it carried has been admitted;
+            // wake the output stream one last time so it can drain what's left of
+            // `manager` and then end, instead of waiting on a notification that
+            // will never come.
+            reader_done.store(true, Ordering::SeqCst);
+            reader_notify.notify_one();
+        });
+
+        Box::pin(futures::stream::unfold(
+            (self.manager, self.notify, output_done),
+            |(manager, notify, done)| async move {
+                loop {
+                    let next = manager.lock().expect("budget manager poisoned").pop();
+                    if let Some(event) = next {
+                        return Some((event, (manager, notify, done)));
+                    }
+                    if done.load(Ordering::SeqCst) {
+                        return None;
+                    }
+                    notify.notified().await;
+                }
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BudgetManager;
+    use crate::event::Event;
+
+    #[test]
+    fn evicts_oldest_first_when_over_budget() {
+        let mut manager = BudgetManager::new("logs".to_owned(), 100);
+
+        manager.admit(40, Event::from("a"));
+        manager.admit(40, Event::from("b"));
+        // Pushes the total to 130, over the 100 byte budget; the first 40 byte
+        // event must be evicted to bring it back under budget.
+        manager.admit(50, Event::from("c"));
+        assert_eq!(manager.current_bytes, 90);
+
+        // The 40 byte "a" event was evicted; "b" and "c" remain, oldest first.
+        assert_eq!(manager.pop().unwrap().as_log().get("message").unwrap(), &"b".into());
+        assert_eq!(manager.pop().unwrap().as_log().get("message").unwrap(), &"c".into());
+        assert!(manager.pop().is_none());
+    }
+
+    #[test]
+    fn evicts_multiple_when_a_single_event_blows_the_budget() {
+        let mut manager = BudgetManager::new("logs".to_owned(), 100);
+
+        manager.admit(30, Event::from("a"));
+        manager.admit(30, Event::from("b"));
+        manager.admit(150, Event::from("c"));
+        assert_eq!(manager.current_bytes, 150);
+
+        assert_eq!(manager.pop().unwrap().as_log().get("message").unwrap(), &"c".into());
+        assert!(manager.pop().is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DataType, TransformConfig, TransformContext};
+use crate::event::Event;
+use crate::transforms::{FunctionTransform, SyncTransform, Transform};
+use vector_core::config::{ComponentKey, Output};
+use vector_core::transform::TransformOutputsBuf;
+
+/// Wraps a single expanded pipeline stage so that every event passing through it
+/// opens a span carrying the `pipeline`, `group` and `stage` it's currently in
+/// (visible as e.g. `my_pipelines -> logs -> foo -> transform[1]`), and so the
+/// stage's throughput is exported as `pipeline_events_in_total`/
+/// `pipeline_events_out_total`, keyed by the generated component key. Covers both
+/// `Transform::Function` stages (`redact`, the per-pipeline transforms) and
+/// `Transform::Synchronous` stages (the router, `filter`/`condition`), since both
+/// have a single per-event call site to wrap; `Transform::Task` stages (`budget`, or
+/// a pipeline transform that builds as a task) aren't wrapped, since a task transform owns its own input stream rather than
+/// being driven one event at a time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstrumentedConfig {
+    pipeline: String,
+    group: String,
+    stage: String,
+    component_key: String,
+    inner: Box<dyn TransformConfig>,
+}
+
+impl InstrumentedConfig {
+    pub fn new(
+        component_key: &ComponentKey,
+        pipeline: impl Into<String>,
+        group: impl Into<String>,
+        stage: impl Into<String>,
+        inner: Box<dyn TransformConfig>,
+    ) -> Self {
+        Self {
+            pipeline: pipeline.into(),
+            group: group.into(),
+            stage: stage.into(),
+            component_key: component_key.id().to_owned(),
+            inner,
+        }
+    }
+}
+
+#[async_trait]
+#[typetag::serde(name = "pipeline_instrumented")]
+impl TransformConfig for InstrumentedConfig {
+    async fn build(&self, context: &TransformContext) -> crate::Result<Transform> {
+        let transform = self.inner.build(context).await?;
+
+        metrics::counter!(
+            "pipeline_events_in_total", 0,
+            "component_key" => self.component_key.clone(),
+        );
+        metrics::counter!(
+            "pipeline_events_out_total", 0,
+            "component_key" => self.component_key.clone(),
+        );
+
+        Ok(match transform {
+            Transform::Function(inner) => Transform::Function(Box::new(Instrumented {
+                inner,
+                pipeline: self.pipeline.clone(),
+                group: self.group.clone(),
+                stage: self.stage.clone(),
+                component_key: self.component_key.clone(),
+            })),
+            Transform::Synchronous(inner) => {
+                Transform::Synchronous(Box::new(InstrumentedSync {
+                    inner,
+                    pipeline: self.pipeline.clone(),
+                    group: self.group.clone(),
+                    stage: self.stage.clone(),
+                    component_key: self.component_key.clone(),
+                }))
+            }
+            // `Task` transforms (`budget`) own their own input stream rather than
+            // being driven one event at a time, so there's no single per-event call
+            // site here to attach a span and counters around.
+            other => other,
+        })
+    }
+
+    fn expand(
+        &mut self,
+        component_key: &ComponentKey,
+        inputs: &[String],
+    ) -> crate::Result<Option<IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)>>>
+    {
+        self.inner.expand(component_key, inputs)
+    }
+
+    fn input_type(&self) -> DataType {
+        self.inner.input_type()
+    }
+
+    fn output_type(&self) -> DataType {
+        self.inner.output_type()
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        self.inner.outputs()
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "pipeline_instrumented"
+    }
+}
+
+#[derive(Clone)]
+struct Instrumented {
+    inner: Box<dyn FunctionTransform>,
+    pipeline: String,
+    group: String,
+    stage: String,
+    component_key: String,
+}
+
+impl FunctionTransform for Instrumented {
+    fn transform(&mut self, output: &mut Vec<Event>, event: Event) {
+        let span = tracing::trace_span!(
+            "pipeline_stage",
+            pipeline = %self.pipeline,
+            group = %self.group,
+            stage = %self.stage,
+        );
+        let _enter = span.enter();
+
+        let before = output.len();
+        self.inner.transform(output, event);
+        let produced = (output.len() - before) as u64;
+
+        metrics::counter!(
+            "pipeline_events_in_total", 1,
+            "component_key" => self.component_key.clone(),
+        );
+        metrics::counter!(
+            "pipeline_events_out_total", produced,
+            "component_key" => self.component_key.clone(),
+        );
+    }
+}
+
+#[derive(Clone)]
+struct InstrumentedSync {
+    inner: Box<dyn SyncTransform>,
+    pipeline: String,
+    group: String,
+    stage: String,
+    component_key: String,
+}
+
+impl SyncTransform for InstrumentedSync {
+    fn transform(&mut self, event: Event, output: &mut TransformOutputsBuf) {
+        let span = tracing::trace_span!(
+            "pipeline_stage",
+            pipeline = %self.pipeline,
+            group = %self.group,
+            stage = %self.stage,
+        );
+        let _enter = span.enter();
+
+        let before = output.len();
+        self.inner.transform(event, output);
+        let produced = (output.len() - before) as u64;
+
+        metrics::counter!(
+            "pipeline_events_in_total", 1,
+            "component_key" => self.component_key.clone(),
+        );
+        metrics::counter!(
+            "pipeline_events_out_total", produced,
+            "component_key" => self.component_key.clone(),
+        );
+    }
+}
@@ -56,9 +56,27 @@
 /// Each pipeline will then be expanded into a list of its transforms and at the end of each
 /// expansion, a `Noop` transform will be added to use the `pipeline` name as an alias
 /// (`my_pipelines.logs.transforms.foo`).
+///
+/// If a pipeline has a `filter`, the events it rejects are not folded back into the
+/// pipeline's main output. Instead they're exposed under their own alias
+/// (`my_pipelines.logs.foo.rejected`) so a dedicated sink or transform can be attached
+/// to everything the pipeline declined to process.
+///
+/// Every component generated during expansion (router, per-group condition, per-pipeline
+/// filter/redact/transform/noop stages) is wrapped so that events passing through it open
+/// a tracing span naming the pipeline, group and stage, and so its throughput is exported
+/// as `pipeline_events_in_total`/`pipeline_events_out_total`, tagged by the generated
+/// component key. Stages that build as task transforms (such as `budget`) get no span
+/// and no per-event counts, and the components of a transform that expands itself are
+/// not wrapped.
+mod budget;
 mod filter;
+mod instrument;
+mod redact;
 mod router;
 
+pub use budget::BudgetConfig;
+
 use crate::conditions::AnyCondition;
 use crate::config::{
     DataType, GenerateConfig, TransformConfig, TransformContext, TransformDescription,
@@ -79,6 +97,8 @@ inventory::submit! {
 pub struct PipelineConfig {
     name: String,
     filter: Option<AnyCondition>,
+    #[serde(default)]
+    redact: Option<redact::RedactConfig>,
     transforms: Vec<Box<dyn TransformConfig>>,
 }
 
@@ -87,6 +107,10 @@ impl PipelineConfig {
     pub fn transforms(&self) -> &Vec<Box<dyn TransformConfig>> {
         &self.transforms
     }
+
+    pub const fn redact(&self) -> &Option<redact::RedactConfig> {
+        &self.redact
+    }
 }
 
 impl Clone for PipelineConfig {
@@ -102,27 +126,74 @@ impl Clone for PipelineConfig {
 }
 
 impl PipelineConfig {
+    fn instrument(
+        &self,
+        component_key: &ComponentKey,
+        group: &str,
+        stage: &str,
+        config: Box<dyn TransformConfig>,
+    ) -> Box<dyn TransformConfig> {
+        Box::new(instrument::InstrumentedConfig::new(
+            component_key,
+            self.name.clone(),
+            group,
+            stage,
+            config,
+        ))
+    }
+
+    /// Expands this pipeline into its constituent components.
+    ///
+    /// Returns the expanded map together with the list of this pipeline's "rejected"
+    /// outputs: the events that failed its `filter` condition, named
+    /// `<pipeline_key>.rejected` so they can be wired to a dead-letter sink instead of
+    /// being silently folded back into the pipeline's main output.
     fn expand(
         &mut self,
         component_key: &ComponentKey,
+        group: &str,
         inputs: &[String],
-    ) -> crate::Result<Option<IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)>>>
-    {
+    ) -> crate::Result<
+        Option<(
+            IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)>,
+            Vec<String>,
+        )>,
+    > {
         let mut map: IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)> =
             IndexMap::new();
+        let mut rejected = Vec::new();
 
         let mut previous: Vec<String> = inputs.into();
 
         if let Some(ref filter) = self.filter {
             let filter_key = component_key.join("filter");
+            let filter_config = self.instrument(
+                &filter_key,
+                group,
+                "filter",
+                Box::new(filter::PipelineFilterConfig::new(filter.clone())),
+            );
+            map.insert(filter_key.clone(), (previous.clone(), filter_config));
+            previous = vec![filter_key.join("truthy").id().to_owned()];
+
+            let rejected_key = component_key.join("rejected");
+            let rejected_config = self.instrument(&rejected_key, group, "rejected", Box::new(Noop));
             map.insert(
-                filter_key.clone(),
+                rejected_key.clone(),
                 (
-                    previous.clone(),
-                    Box::new(filter::PipelineFilterConfig::new(filter.clone())),
+                    vec![filter_key.join("falsy").id().to_owned()],
+                    rejected_config,
                 ),
             );
-            previous = vec![filter_key.join("truthy").id().to_owned()];
+            rejected.push(rejected_key.id().to_owned());
+        }
+
+        if let Some(ref redact) = self.redact {
+            let redact_key = component_key.join("redact");
+            let redact_config =
+                self.instrument(&redact_key, group, "redact", Box::new(redact.clone()));
+            map.insert(redact_key.clone(), (previous.clone(), redact_config));
+            previous = vec![redact_key.id().to_owned()];
         }
 
         for (index, transform) in self.transforms.iter_mut().enumerate() {
@@ -131,17 +202,17 @@ impl PipelineConfig {
                 previous = vec![transform_key.id().to_owned()];
                 map.extend(expanded);
             } else {
-                map.insert(transform_key, (previous.clone(), transform.clone()));
+                let stage = format!("transform[{}]", index);
+                let transform_config =
+                    self.instrument(&transform_key, group, &stage, transform.clone());
+                map.insert(transform_key, (previous.clone(), transform_config));
             }
         }
 
-        if self.filter.is_some() {
-            previous.push(component_key.join("filter").join("falsy").id().to_owned());
-        } else {
-            map.insert(component_key.clone(), (previous, Box::new(Noop)));
-        }
+        let noop_config = self.instrument(component_key, group, "noop", Box::new(Noop));
+        map.insert(component_key.clone(), (previous, noop_config));
 
-        Ok(Some(map))
+        Ok(Some((map, rejected)))
     }
 }
 
@@ -151,6 +222,13 @@ impl PipelineConfig {
 pub struct EventTypeConfig {
     #[serde(default)]
     order: Option<Vec<String>>,
+    #[serde(default)]
+    budget: Option<budget::BudgetConfig>,
+    /// An optional predicate evaluated against arbitrary event metadata. When set, an
+    /// event is only routed into this group if it also matches this condition, in
+    /// addition to already matching the group's data type.
+    #[serde(default)]
+    condition: Option<AnyCondition>,
     pipelines: IndexMap<String, PipelineConfig>,
 }
 
@@ -163,6 +241,14 @@ impl EventTypeConfig {
     pub const fn pipelines(&self) -> &IndexMap<String, PipelineConfig> {
         &self.pipelines
     }
+
+    pub const fn budget(&self) -> &Option<budget::BudgetConfig> {
+        &self.budget
+    }
+
+    pub const fn condition(&self) -> &Option<AnyCondition> {
+        &self.condition
+    }
 }
 
 impl EventTypeConfig {
@@ -178,29 +264,64 @@ impl EventTypeConfig {
         }
     }
 
+    /// Expands this group's pipelines, returning the expanded map together with the
+    /// keys of the `rejected` outputs of every pipeline in the group. Those outputs
+    /// are already components of the returned map, addressable by their own keys.
+    ///
+    /// `group` is the group's own name (`logs`, `metrics` or `traces`), carried into
+    /// every expanded stage's tracing span.
     fn expand(
         &mut self,
         component_key: &ComponentKey,
+        group: &str,
         inputs: &[String],
-    ) -> crate::Result<Option<IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)>>>
-    {
+    ) -> crate::Result<
+        Option<(
+            IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)>,
+            Vec<String>,
+        )>,
+    > {
         let mut map: IndexMap<ComponentKey, (Vec<String>, Box<dyn TransformConfig>)> =
             IndexMap::new();
+        let mut rejected = Vec::new();
 
         let mut previous: Vec<String> = inputs.into();
+
+        if let Some(ref budget) = self.budget {
+            let budget_key = component_key.join("budget");
+            let budget = budget.clone().for_pipeline(component_key.id().to_owned());
+            let budget_config = instrument::InstrumentedConfig::new(
+                &budget_key,
+                "",
+                group,
+                "budget",
+                Box::new(budget),
+            );
+            map.insert(
+                budget_key.clone(),
+                (previous.clone(), Box::new(budget_config)),
+            );
+            previous = vec![budget_key.id().to_owned()];
+        }
+
         for name in self.names() {
             if let Some(pipeline) = self.pipelines.get_mut(&name) {
                 let pipeline_key = component_key.join(name);
-                if let Some(expanded) = pipeline.expand(&pipeline_key, &previous)? {
+                if let Some((expanded, pipeline_rejected)) =
+                    pipeline.expand(&pipeline_key, group, &previous)?
+                {
                     map.extend(expanded);
+                    rejected.extend(pipeline_rejected);
                     previous = vec![pipeline_key.id().to_owned()];
                 }
             }
         }
 
-        map.insert(component_key.clone(), (previous, Box::new(Noop)));
+        let group_noop =
+            instrument::InstrumentedConfig::new(component_key, "", group, "noop", Box::new(Noop));
+        map.insert(component_key.clone(), (previous, Box::new(group_noop)));
 
-        Ok(Some(map))
+        Ok(Some((map, rejected)))
     }
 }
 
@@ -211,8 +332,17 @@ pub struct PipelinesConfig {
     logs: EventTypeConfig,
     #[serde(default)]
     metrics: EventTypeConfig,
+    #[serde(default)]
+    traces: EventTypeConfig,
+    /// Which of the `logs`, `metrics` and `traces` groups to expand, and in what
+    /// order their components are generated. A group left out of this list isn't
+    /// expanded at all. Defaults to `["logs", "metrics", "traces"]`.
+    #[serde(default)]
+    order: Option<Vec<String>>,
 }
 
+const EVENT_TYPE_GROUPS: &[&str] = &["logs", "metrics", "traces"];
+
 #[cfg(test)]
 impl PipelinesConfig {
     pub const fn logs(&self) -> &EventTypeConfig {
@@ -222,6 +352,30 @@ impl PipelinesConfig {
     pub const fn metrics(&self) -> &EventTypeConfig {
         &self.metrics
     }
+
+    pub const fn traces(&self) -> &EventTypeConfig {
+        &self.traces
+    }
+}
+
+impl PipelinesConfig {
+    fn group_order(&self) -> Vec<String> {
+        self.order.clone().unwrap_or_else(|| {
+            EVENT_TYPE_GROUPS
+                .iter()
+                .map(|name| (*name).to_owned())
+                .collect()
+        })
+    }
+
+    fn group_mut(&mut self, name: &str) -> Option<&mut EventTypeConfig> {
+        match name {
+            "logs" => Some(&mut self.logs),
+            "metrics" => Some(&mut self.metrics),
+            "traces" => Some(&mut self.traces),
+            _ => None,
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -233,12 +387,24 @@ impl TransformConfig for PipelinesConfig {
 
     /// Expands the pipelines in multiple components
     ///
-    /// `id.router`: dispatch function to dispatch logs and events depending on type
+    /// `id.router`: dispatch function to dispatch logs, metrics and traces depending
+    /// on event type
     /// `id.router.logs`: output of the dispatch function for logs
-    /// `id.router.metrics`: output of the dispatch function fo metrics
+    /// `id.router.metrics`: output of the dispatch function for metrics
+    /// `id.router.traces`: output of the dispatch function for traces
     /// `id.logs`: id of the unexpanded transform for logs
     /// `id.metrics`: id of the unexpanded transform for metrics
-    /// `id`: noop transform to join metrics and logs stream
+    /// `id.traces`: id of the unexpanded transform for traces
+    /// `id`: noop transform to join the logs, metrics and traces streams
+    ///
+    /// Each pipeline that declares a `filter` also gets a `<pipeline>.rejected`
+    /// output carrying the events that failed the filter, so they can be routed to a
+    /// dead-letter sink instead of being silently dropped.
+    ///
+    /// Each group (`logs`, `metrics`, `traces`) may also declare a `condition`: an
+    /// event that matches the group's data type but fails its condition is dropped,
+    /// the same way an event that fails a pipeline's `filter` would be if that
+    /// pipeline had no `rejected` output wired up for it.
     fn expand(
         &mut self,
         component_key: &ComponentKey,
@@ -252,26 +418,75 @@ impl TransformConfig for PipelinesConfig {
             router_key.clone(),
             (
                 inputs.into(),
-                Box::new(router::EventRouterConfig::default()),
+                Box::new(instrument::InstrumentedConfig::new(
+                    &router_key,
+                    "",
+                    "",
+                    "router",
+                    Box::new(router::EventRouterConfig::default()),
+                )),
             ),
         );
-        let mut outputs = Vec::with_capacity(2);
-
-        let logs_inputs = vec![router_key.join("logs").id().to_owned()];
-        let logs_key = component_key.join("logs");
-        if let Some(expanded) = self.logs.expand(&logs_key, &logs_inputs)? {
-            map.extend(expanded);
-            outputs.push(logs_key.id().to_owned());
-        }
-
-        let metrics_inputs = vec![router_key.join("metrics").id().to_owned()];
-        let metrics_key = component_key.join("metrics");
-        if let Some(expanded) = self.metrics.expand(&metrics_key, &metrics_inputs)? {
-            map.extend(expanded);
-            outputs.push(metrics_key.id().to_owned());
+        let mut outputs = Vec::with_capacity(EVENT_TYPE_GROUPS.len());
+
+        for name in self.group_order() {
+            let group = match self.group_mut(&name) {
+                Some(group) => group,
+                None => continue,
+            };
+
+            let router_port = router_key.join(name.as_str()).id().to_owned();
+            let group_key = component_key.join(name.as_str());
+
+            // An event the router has already routed to this group is guaranteed to
+            // match its data type, so there's nothing else for the group's own
+            // `condition` to fall through to: every other group only accepts its own
+            // data type too, and this group's is the only one this event has.
+            // Whatever fails `condition` is simply dropped.
+            let entry_inputs = if let Some(condition) = group.condition.clone() {
+                let condition_key = group_key.join("condition");
+                map.insert(
+                    condition_key.clone(),
+                    (
+                        vec![router_port],
+                        Box::new(instrument::InstrumentedConfig::new(
+                            &condition_key,
+                            "",
+                            name.as_str(),
+                            "condition",
+                            Box::new(filter::PipelineFilterConfig::new(condition)),
+                        )),
+                    ),
+                );
+                vec![condition_key.join("truthy").id().to_owned()]
+            } else {
+                vec![router_port]
+            };
+
+            // Each pipeline's `rejected` noop (if it has a `filter`) was already
+            // inserted into `map` by `group.expand`, addressable directly by its own
+            // component key (e.g. `my_pipelines.logs.foo.rejected`), the same way
+            // every other generated stage is — there's nothing further to thread
+            // through here to make it a first-class output.
+            if let Some((expanded, _rejected)) = group.expand(&group_key, &name, &entry_inputs)? {
+                map.extend(expanded);
+                outputs.push(group_key.id().to_owned());
+            }
         }
 
-        map.insert(component_key.clone(), (outputs, Box::new(Noop)));
+        map.insert(
+            component_key.clone(),
+            (
+                outputs,
+                Box::new(instrument::InstrumentedConfig::new(
+                    component_key,
+                    "",
+                    "",
+                    "noop",
+                    Box::new(Noop),
+                )),
+            ),
+        );
 
         Ok(Some(map))
     }
@@ -295,9 +510,11 @@ impl TransformConfig for PipelinesConfig {
         } else {
             let mut nodes = parents.clone();
             nodes.insert(self.transform_type());
-            for pipeline in self.logs.pipelines.values() {
-                for transform in pipeline.transforms.iter() {
-                    transform.nestable(&nodes)?;
+            for group in [&self.logs, &self.metrics, &self.traces] {
+                for pipeline in group.pipelines.values() {
+                    for transform in pipeline.transforms.iter() {
+                        transform.nestable(&nodes)?;
+                    }
                 }
             }
             Ok(())
@@ -382,14 +599,128 @@ mod tests {
             vec![
                 "foo.router",
                 "foo.logs.foo.filter",
+                "foo.logs.foo.rejected",
                 "foo.logs.foo.0",
                 "foo.logs.foo.1",
+                "foo.logs.foo",
                 "foo.logs.bar.0",
                 "foo.logs.bar",
                 "foo.logs",
                 "foo.metrics",
+                "foo.traces",
                 "foo"
             ],
         );
     }
+
+    #[test]
+    fn expanding_exposes_rejected_output() {
+        let config = PipelinesConfig::generate_config();
+        let mut config: PipelinesConfig = config.try_into().unwrap();
+        let inputs = vec!["syslog".to_owned()];
+        let name = ComponentKey::from("foo");
+        let expanded = config.expand(&name, &inputs).unwrap().unwrap();
+
+        let rejected_key = ComponentKey::from("foo.logs.foo.rejected");
+        let (rejected_inputs, _) = expanded.get(&rejected_key).unwrap();
+        assert_eq!(
+            rejected_inputs,
+            &vec!["foo.logs.foo.filter.falsy".to_owned()]
+        );
+
+        // The pipeline's own alias must still be produced even though it has a
+        // filter, so the next pipeline in `order` can take it as an input.
+        let pipeline_key = ComponentKey::from("foo.logs.foo");
+        assert!(expanded.get(&pipeline_key).is_some());
+    }
+
+    #[test]
+    fn expanding_with_redact() {
+        let mut config = PipelinesConfig::from_toml(indoc::indoc! {r#"
+            [logs]
+            order = ["foo"]
+
+            [logs.pipelines.foo]
+            name = "foo pipeline"
+
+            [logs.pipelines.foo.redact]
+            [logs.pipelines.foo.redact.rules.email]
+            pattern = "[\\w.]+@[\\w.]+"
+
+            [[logs.pipelines.foo.transforms]]
+            type = "filter"
+            condition = ""
+        "#});
+        let inputs = vec!["syslog".to_owned()];
+        let name = ComponentKey::from("foo");
+        let expanded = config.expand(&name, &inputs).unwrap().unwrap();
+        assert_eq!(
+            expanded
+                .keys()
+                .map(|key| key.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "foo.router",
+                "foo.logs.foo.redact",
+                "foo.logs.foo.0",
+                "foo.logs.foo",
+                "foo.logs",
+                "foo.metrics",
+                "foo.traces",
+                "foo"
+            ],
+        );
+    }
+
+    #[test]
+    fn expanding_with_condition_drops_events_that_fail_it() {
+        let mut config = PipelinesConfig::from_toml(indoc::indoc! {r#"
+            [logs]
+            order = ["foo"]
+
+            [logs.condition]
+            type = "datadog_search"
+            source = "source:s3"
+
+            [logs.pipelines.foo]
+            name = "foo pipeline"
+
+            [[logs.pipelines.foo.transforms]]
+            type = "filter"
+            condition = ""
+        "#});
+        let inputs = vec!["syslog".to_owned()];
+        let name = ComponentKey::from("foo");
+        let expanded = config.expand(&name, &inputs).unwrap().unwrap();
+
+        let condition_key = ComponentKey::from("foo.logs.condition");
+        let (condition_inputs, _) = expanded.get(&condition_key).unwrap();
+        assert_eq!(condition_inputs, &vec!["foo.router.logs".to_owned()]);
+
+        // An event the router sent to `logs` is already guaranteed to be a log
+        // event, so there's no other group its `logs.condition.falsy` output could
+        // ever legitimately feed: it's simply not wired anywhere, and the event is
+        // dropped.
+        assert!(expanded.values().all(|(inputs, _)| !inputs
+            .iter()
+            .any(|input| input == "foo.logs.condition.falsy")));
+    }
+
+    #[test]
+    fn expanding_wraps_every_stage_with_instrumentation() {
+        let config = PipelinesConfig::generate_config();
+        let mut config: PipelinesConfig = config.try_into().unwrap();
+        let inputs = vec!["syslog".to_owned()];
+        let name = ComponentKey::from("foo");
+        let expanded = config.expand(&name, &inputs).unwrap().unwrap();
+
+        for (key, (_, transform)) in expanded.iter() {
+            assert_eq!(
+                transform.transform_type(),
+                "pipeline_instrumented",
+                "{} was not wrapped with instrumentation",
+                key
+            );
+        }
+    }
 }
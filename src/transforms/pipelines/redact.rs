@@ -0,0 +1,281 @@
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::config::{DataType, TransformConfig, TransformContext};
+use crate::event::Event;
+use crate::transforms::{FunctionTransform, Transform};
+
+/// A single named redaction rule: a regex plus the set of field paths it should scan.
+/// When `paths` is unset, every string field on the event is scanned.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RedactRuleConfig {
+    pattern: String,
+    #[serde(default)]
+    paths: Option<Vec<String>>,
+    /// A string known to match this rule's `pattern`, used by the canary to verify the
+    /// rule is actually wired in. Rules without a sample are skipped by the canary:
+    /// a name-derived guess can't be trusted to match an arbitrary regex, and a false
+    /// alarm on every tick is worse than no check at all.
+    #[serde(default)]
+    canary_sample: Option<String>,
+}
+
+/// Periodically synthesizes a self-check event containing strings that must match
+/// each configured rule, so operators can verify redaction is actually wired in.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CanaryConfig {
+    interval_secs: u64,
+}
+
+/// Configuration of the optional `redact` stage of a pipeline. It runs before the
+/// pipeline's transforms, mirroring how `filter` is injected ahead of them.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedactConfig {
+    rules: IndexMap<String, RedactRuleConfig>,
+    #[serde(default)]
+    canary: Option<CanaryConfig>,
+}
+
+impl RedactConfig {
+    pub const fn new(
+        rules: IndexMap<String, RedactRuleConfig>,
+        canary: Option<CanaryConfig>,
+    ) -> Self {
+        Self { rules, canary }
+    }
+
+    fn compile(&self) -> crate::Result<Vec<CompiledRule>> {
+        self.rules
+            .iter()
+            .map(|(name, rule)| {
+                Ok(CompiledRule {
+                    name: name.clone(),
+                    regex: Regex::new(&rule.pattern)?,
+                    paths: rule.paths.clone(),
+                    canary_sample: rule.canary_sample.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+#[typetag::serde(name = "pipeline_redact")]
+impl TransformConfig for RedactConfig {
+    async fn build(&self, _context: &TransformContext) -> crate::Result<Transform> {
+        let rules = self.compile()?;
+        let (shutdown, shutdown_rx) = ShutdownGuard::new();
+
+        if let Some(canary) = &self.canary {
+            // The canary's own copy of the stage isn't itself a running transform,
+            // so it gets its own inert guard; it's `shutdown_rx`, tied to the real
+            // running `Redact` below, that actually ends this task.
+            let (canary_shutdown, _unused_rx) = ShutdownGuard::new();
+            spawn_canary(
+                Redact {
+                    rules: rules.clone(),
+                    _shutdown: canary_shutdown,
+                },
+                Duration::from_secs(canary.interval_secs),
+                shutdown_rx,
+            );
+        }
+
+        Ok(Transform::function(Redact { rules, _shutdown: shutdown }))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "pipeline_redact"
+    }
+}
+
+#[derive(Clone)]
+struct CompiledRule {
+    name: String,
+    regex: Regex,
+    paths: Option<Vec<String>>,
+    canary_sample: Option<String>,
+}
+
+impl CompiledRule {
+    fn marker(&self) -> String {
+        format!("<REDACTED:{}>", self.name)
+    }
+
+    /// Applies the rule to a single string value, returning the redacted string and
+    /// the number of matches replaced.
+    fn redact_str(&self, input: &str) -> (String, usize) {
+        let mut count = 0;
+        let marker = self.marker();
+        let replaced = self
+            .regex
+            .replace_all(input, |_: &regex::Captures| {
+                count += 1;
+                marker.clone()
+            })
+            .into_owned();
+        (replaced, count)
+    }
+}
+
+/// The runtime `redact` stage: walks the configured (or all) string fields of every
+/// event and replaces each rule's matches with a canonical `<REDACTED:{rule_name}>`
+/// marker, tracking a per-rule replacement count as an internal metric.
+#[derive(Clone)]
+struct Redact {
+    rules: Vec<CompiledRule>,
+    /// Held only so its `Drop` can tell the canary task (if any) to stop once every
+    /// clone of this running transform is gone; never read otherwise.
+    _shutdown: ShutdownGuard,
+}
+
+impl FunctionTransform for Redact {
+    fn transform(&mut self, output: &mut Vec<Event>, mut event: Event) {
+        // `redact` is a stage of `PipelineConfig`, which is also valid in the
+        // `metrics` and `traces` groups; only log events have fields to scan.
+        let log = match event {
+            Event::Log(ref mut log) => log,
+            other => {
+                output.push(other);
+                return;
+            }
+        };
+
+        for rule in &self.rules {
+            let mut total = 0;
+
+            match &rule.paths {
+                Some(paths) => {
+                    for path in paths {
+                        if let Some(value) = log.get_mut(path.as_str()) {
+                            total += redact_value(value, rule);
+                        }
+                    }
+                }
+                None => {
+                    // `LogEvent` only exposes a mutable accessor per key, not a
+                    // mutable iterator over every field, so collect the keys first.
+                    for key in log.keys().collect::<Vec<_>>() {
+                        if let Some(value) = log.get_mut(key.as_str()) {
+                            total += redact_value(value, rule);
+                        }
+                    }
+                }
+            }
+
+            if total > 0 {
+                metrics::counter!("pipeline_redactions_total", total as u64, "rule" => rule.name.clone());
+            }
+        }
+
+        output.push(event);
+    }
+}
+
+fn redact_value(value: &mut crate::event::Value, rule: &CompiledRule) -> usize {
+    if let crate::event::Value::Bytes(bytes) = value {
+        if let Ok(input) = std::str::from_utf8(bytes) {
+            let (redacted, count) = rule.redact_str(input);
+            if count > 0 {
+                *value = crate::event::Value::Bytes(redacted.into());
+            }
+            return count;
+        }
+    }
+    0
+}
+
+/// A drop-triggered shutdown signal: the paired receiver resolves as soon as
+/// every clone of a guard is gone (i.e. the transform instance it's embedded in
+/// is gone, reloaded away by a new config or the topology stopping), since a
+/// `oneshot::Sender` already resolves its receiver the moment it's dropped.
+/// Wrapping it in `Arc` is what extends that to "every clone", since `Redact`
+/// (and this guard with it) is cloned across workers like any other transform.
+#[derive(Clone)]
+struct ShutdownGuard(#[allow(dead_code)] Arc<oneshot::Sender<()>>);
+
+impl ShutdownGuard {
+    fn new() -> (Self, oneshot::Receiver<()>) {
+        let (tx, rx) = oneshot::channel();
+        (Self(Arc::new(tx)), rx)
+    }
+}
+
+/// Spawns a background task that, on `interval`, runs each rule's configured
+/// `canary_sample` through the actual `Redact` transform and asserts the rule still
+/// fired, logging an error and incrementing a metric for any rule that doesn't.
+/// Driving the sample through `redact.transform` rather than calling `redact_str`
+/// directly on the rule is the point: it exercises the same code path a live event
+/// takes, so a canary failure means the stage itself isn't redacting, not just that
+/// a regex is broken. Rules without a sample are skipped, since there's no way to
+/// derive a string that must match an arbitrary regex from its name alone.
+///
+/// `shutdown` resolves once the running `Redact` stage this canary is checking has
+/// been dropped, ending the loop; without this, every config reload leaked another
+/// copy of this task ticking forever in the background.
+fn spawn_canary(mut redact: Redact, interval: Duration, mut shutdown: oneshot::Receiver<()>) {
+    if interval.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = &mut shutdown => return,
+            }
+
+            for rule in redact.rules.clone() {
+                let sample = match &rule.canary_sample {
+                    Some(sample) => sample.clone(),
+                    None => continue,
+                };
+
+                // Put the sample where the rule actually looks: its first configured
+                // path, or `message` when it scans every field.
+                let field = rule
+                    .paths
+                    .as_ref()
+                    .and_then(|paths| paths.first())
+                    .cloned()
+                    .unwrap_or_else(|| "message".to_owned());
+
+                let mut log = crate::event::LogEvent::default();
+                log.insert(field.as_str(), sample);
+
+                let mut output = Vec::new();
+                redact.transform(&mut output, Event::Log(log));
+
+                let fired = output
+                    .pop()
+                    .and_then(|event| event.into_log().remove(field.as_str()))
+                    .map(|value| value.to_string_lossy().contains(&rule.marker()))
+                    .unwrap_or(false);
+
+                if !fired {
+                    metrics::counter!("pipeline_redact_canary_failures_total", 1, "rule" => rule.name.clone());
+                    tracing::error!(
+                        message = "Redaction canary failed to match rule; redaction may not be wired in correctly.",
+                        rule = %rule.name,
+                    );
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DataType, TransformConfig, TransformContext};
+use crate::event::Event;
+use crate::transforms::{SyncTransform, Transform};
+use vector_core::config::Output;
+use vector_core::transform::TransformOutputsBuf;
+
+/// Dispatches every incoming event to one of the `logs`, `metrics` or `traces` named
+/// outputs depending on its [`Event`] variant, so each of the pipelines transform's
+/// per-type groups only ever sees events of its own data type.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct EventRouterConfig;
+
+#[async_trait]
+#[typetag::serde(name = "pipeline_router")]
+impl TransformConfig for EventRouterConfig {
+    async fn build(&self, _context: &TransformContext) -> crate::Result<Transform> {
+        Ok(Transform::synchronous(EventRouter))
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        vec![
+            Output::from(("logs", DataType::Log)),
+            Output::from(("metrics", DataType::Metric)),
+            Output::from(("traces", DataType::Trace)),
+        ]
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "pipeline_router"
+    }
+}
+
+/// Routes each event to the named output matching its own [`Event`] variant. This
+/// needs a named output per event rather than the single implicit one a
+/// `FunctionTransform` provides, so it's a `SyncTransform` instead, matching how
+/// `filter`'s `truthy`/`falsy` ports are produced.
+#[derive(Clone, Copy, Debug, Default)]
+struct EventRouter;
+
+impl EventRouter {
+    const fn port_for(event: &Event) -> &'static str {
+        match event {
+            Event::Log(_) => "logs",
+            Event::Metric(_) => "metrics",
+            Event::Trace(_) => "traces",
+        }
+    }
+}
+
+impl SyncTransform for EventRouter {
+    fn transform(&mut self, event: Event, output: &mut TransformOutputsBuf) {
+        let port = Self::port_for(&event);
+        output.push(Some(port), event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventRouter;
+    use crate::config::DataType;
+    use crate::event::{Event, LogEvent, Metric, MetricKind, MetricValue, TraceEvent};
+    use crate::transforms::SyncTransform;
+    use vector_core::config::Output;
+    use vector_core::transform::TransformOutputsBuf;
+
+    #[test]
+    fn port_for_matches_each_event_variant() {
+        assert_eq!(EventRouter::port_for(&Event::Log(LogEvent::default())), "logs");
+        assert_eq!(
+            EventRouter::port_for(&Event::Metric(Metric::new(
+                "test",
+                MetricKind::Absolute,
+                MetricValue::Counter { value: 1.0 },
+            ))),
+            "metrics"
+        );
+        assert_eq!(EventRouter::port_for(&Event::Trace(TraceEvent::default())), "traces");
+    }
+
+    #[test]
+    fn transform_pushes_only_to_the_matching_named_output() {
+        let mut outputs = TransformOutputsBuf::new_with_capacity(
+            vec![
+                Output::from(("logs", DataType::Log)),
+                Output::from(("metrics", DataType::Metric)),
+                Output::from(("traces", DataType::Trace)),
+            ],
+            1,
+        );
+
+        EventRouter.transform(
+            Event::Metric(Metric::new(
+                "test",
+                MetricKind::Absolute,
+                MetricValue::Counter { value: 1.0 },
+            )),
+            &mut outputs,
+        );
+
+        let mut named = outputs.take_all_named();
+        assert_eq!(named.remove("metrics").unwrap().drain().count(), 1);
+        assert_eq!(named.remove("logs").unwrap().drain().count(), 0);
+        assert_eq!(named.remove("traces").unwrap().drain().count(), 0);
+    }
+}